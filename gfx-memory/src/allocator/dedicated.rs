@@ -0,0 +1,87 @@
+//! A standalone block backed by its own `hal::Memory` object.
+
+use std::sync::Arc;
+
+use crate::{mapping::MappedRange, Size};
+
+/// A block of memory backed by its own dedicated `hal::Memory` object rather
+/// than sub-allocated out of a shared chunk.
+///
+/// Used for resources that must not share an allocation (externally imported
+/// or exported memory) or that are large enough that sub-allocating would
+/// waste whatever remains of the chunk.
+#[derive(Debug)]
+pub struct DedicatedBlock<B: hal::Backend> {
+    memory: B::Memory,
+    properties: hal::memory::Properties,
+    size: Size,
+    name: Option<Arc<str>>,
+    // Index into `MemoryType::dedicated`, used to remove this allocation's
+    // entry from the report registry on free.
+    slot: usize,
+    device_address: bool,
+}
+
+impl<B: hal::Backend> DedicatedBlock<B> {
+    pub(crate) fn new(
+        memory: B::Memory,
+        properties: hal::memory::Properties,
+        size: Size,
+        name: Option<Arc<str>>,
+        slot: usize,
+        device_address: bool,
+    ) -> Self {
+        DedicatedBlock {
+            memory,
+            properties,
+            size,
+            name,
+            slot,
+            device_address,
+        }
+    }
+
+    pub(crate) fn size(&self) -> Size {
+        self.size
+    }
+
+    pub(crate) fn properties(&self) -> hal::memory::Properties {
+        self.properties
+    }
+
+    pub(crate) fn memory(&self) -> &B::Memory {
+        &self.memory
+    }
+
+    pub(crate) fn segment(&self) -> hal::memory::Segment {
+        hal::memory::Segment::ALL
+    }
+
+    pub(crate) fn map<'a>(
+        &'a mut self,
+        device: &B::Device,
+        segment: hal::memory::Segment,
+    ) -> Result<MappedRange<'a, B>, hal::device::MapError> {
+        let ptr = unsafe { hal::device::Device::map_memory(device, &self.memory, segment.clone())? };
+        Ok(MappedRange::new(
+            &self.memory,
+            segment,
+            std::ptr::NonNull::new(ptr).expect("`map_memory` returned a null pointer"),
+        ))
+    }
+
+    pub(crate) fn slot(&self) -> usize {
+        self.slot
+    }
+
+    /// Whether this block's `hal::Memory` object was allocated with the
+    /// device-address allocation flag.
+    pub(crate) fn device_address(&self) -> bool {
+        self.device_address
+    }
+
+    /// Consume the block and hand the underlying memory object back to the caller.
+    pub(crate) fn unwrap_memory(self) -> B::Memory {
+        self.memory
+    }
+}
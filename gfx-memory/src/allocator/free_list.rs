@@ -0,0 +1,443 @@
+//! Free-list sub-allocator with coalescing.
+//!
+//! Unlike the linear and buddy allocators, a chunk here can service
+//! allocations and frees in any order: each chunk keeps a list of free
+//! `(offset, size)` regions, picks the first one that fits (after rounding up
+//! for alignment) and splits off whatever remains. Freeing reinserts the
+//! region and merges it with an immediately adjacent free region on either
+//! side, which keeps long-lived, mixed-lifetime traffic from fragmenting a
+//! chunk the way a pure bump or buddy allocator would.
+
+use std::sync::Arc;
+
+use super::Allocator;
+use crate::{
+    mapping::MappedRange,
+    report::{BlockReport, ChunkReport},
+    util::align_up,
+    Size,
+};
+
+/// Config for the free-list sub-allocator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FreeListConfig {
+    /// Size of a chunk requested from the device when no existing chunk has
+    /// a free region large enough to serve a request.
+    pub block_size: Size,
+}
+
+/// Sub-block handed out by the [`FreeListAllocator`].
+///
+/// Chunks are boxed so their address is stable for the lifetime of any block
+/// carved out of them, even as the allocator's chunk list grows; `memory`
+/// points at the owning chunk's `B::Memory` for as long as this block lives.
+#[derive(Debug)]
+pub struct FreeListBlock<B: hal::Backend> {
+    chunk: usize,
+    memory: *const B::Memory,
+    // Start and length of the free region this block was carved from,
+    // needed to reinsert (and coalesce) the exact same span on free.
+    region_offset: Size,
+    region_size: Size,
+    offset: Size,
+    size: Size,
+    properties: hal::memory::Properties,
+    name: Option<Arc<str>>,
+}
+
+unsafe impl<B: hal::Backend> Send for FreeListBlock<B> {}
+unsafe impl<B: hal::Backend> Sync for FreeListBlock<B> {}
+
+impl<B: hal::Backend> FreeListBlock<B> {
+    pub(crate) fn size(&self) -> Size {
+        self.size
+    }
+
+    pub(crate) fn properties(&self) -> hal::memory::Properties {
+        self.properties
+    }
+
+    pub(crate) fn memory(&self) -> &B::Memory {
+        unsafe { &*self.memory }
+    }
+
+    pub(crate) fn segment(&self) -> hal::memory::Segment {
+        hal::memory::Segment {
+            offset: self.offset as _,
+            size: Some(self.size as _),
+        }
+    }
+
+    pub(crate) fn map<'a>(
+        &'a mut self,
+        device: &B::Device,
+        segment: hal::memory::Segment,
+    ) -> Result<MappedRange<'a, B>, hal::device::MapError> {
+        let memory = self.memory();
+        let ptr = unsafe { hal::device::Device::map_memory(device, memory, segment.clone())? };
+        Ok(MappedRange::new(
+            memory,
+            segment,
+            std::ptr::NonNull::new(ptr).expect("`map_memory` returned a null pointer"),
+        ))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct FreeRegion {
+    offset: Size,
+    size: Size,
+}
+
+/// First-fit allocation with coalescing over a chunk's free `(offset, size)`
+/// regions. Kept independent of the backing `hal::Memory` object so this
+/// bit-twiddling can be unit tested without a `hal::Backend`.
+#[derive(Debug)]
+struct FreeRegions {
+    // Sorted by `offset`, no two entries ever adjacent (they get merged on insert).
+    regions: Vec<FreeRegion>,
+}
+
+impl FreeRegions {
+    fn new(size: Size) -> Self {
+        FreeRegions {
+            regions: vec![FreeRegion { offset: 0, size }],
+        }
+    }
+
+    /// First-fit: find a free region that fits `size` once `offset` is
+    /// rounded up to `align`, split off the remainder and return the
+    /// `(offset, region_offset, region_size)` of the carved-out block.
+    fn alloc(&mut self, size: Size, align: Size) -> Option<(Size, Size, Size)> {
+        let (index, offset, region) = self.regions.iter().enumerate().find_map(|(index, region)| {
+            let offset = align_up(region.offset, align);
+            if offset + size <= region.offset + region.size {
+                Some((index, offset, *region))
+            } else {
+                None
+            }
+        })?;
+
+        self.regions.remove(index);
+        let consumed_end = offset + size;
+        let remainder_size = region.offset + region.size - consumed_end;
+        if remainder_size > 0 {
+            self.regions.insert(
+                index,
+                FreeRegion {
+                    offset: consumed_end,
+                    size: remainder_size,
+                },
+            );
+        }
+
+        Some((offset, region.offset, consumed_end - region.offset))
+    }
+
+    /// Reinsert a freed `(region_offset, region_size)` span, merging with an
+    /// immediately adjacent free region on either side.
+    fn free(&mut self, region_offset: Size, region_size: Size) {
+        let index = self
+            .regions
+            .binary_search_by_key(&region_offset, |r| r.offset)
+            .unwrap_or_else(|index| index);
+        self.regions.insert(
+            index,
+            FreeRegion {
+                offset: region_offset,
+                size: region_size,
+            },
+        );
+
+        // Coalesce with the following region first so the index of the
+        // preceding region (if any) stays valid.
+        if index + 1 < self.regions.len()
+            && self.regions[index].offset + self.regions[index].size == self.regions[index + 1].offset
+        {
+            let next = self.regions.remove(index + 1);
+            self.regions[index].size += next.size;
+        }
+        if index > 0 && self.regions[index - 1].offset + self.regions[index - 1].size == self.regions[index].offset {
+            let current = self.regions.remove(index);
+            self.regions[index - 1].size += current.size;
+        }
+    }
+}
+
+/// A live sub-block tracked purely for `Heaps::report`.
+#[derive(Debug)]
+struct LiveBlock {
+    offset: Size,
+    size: Size,
+    name: Option<Arc<str>>,
+}
+
+struct FreeListChunk<B: hal::Backend> {
+    memory: B::Memory,
+    size: Size,
+    free: FreeRegions,
+    live: Vec<LiveBlock>,
+}
+
+impl<B: hal::Backend> FreeListChunk<B> {
+    fn new(memory: B::Memory, size: Size) -> Self {
+        FreeListChunk {
+            memory,
+            size,
+            free: FreeRegions::new(size),
+            live: Vec::new(),
+        }
+    }
+
+    fn alloc(&mut self, size: Size, align: Size, name: Option<Arc<str>>) -> Option<(Size, Size, Size)> {
+        let (offset, region_offset, region_size) = self.free.alloc(size, align)?;
+        self.live.push(LiveBlock { offset, size, name });
+        Some((offset, region_offset, region_size))
+    }
+
+    fn free(&mut self, offset: Size, region_offset: Size, region_size: Size) {
+        self.live.retain(|live| live.offset != offset);
+        self.free.free(region_offset, region_size);
+    }
+
+    fn is_entirely_free(&self) -> bool {
+        self.live.is_empty()
+    }
+}
+
+/// Free-list sub-allocator used for long-lived resources with mixed
+/// lifetimes, where the buddy allocator's power-of-two rounding and the
+/// linear allocator's whole-chunk drain requirement both fragment badly.
+pub(crate) struct FreeListAllocator<B: hal::Backend> {
+    id: hal::MemoryTypeId,
+    properties: hal::memory::Properties,
+    config: FreeListConfig,
+    max_chunk_size: Size,
+    chunks: Vec<Option<Box<FreeListChunk<B>>>>,
+}
+
+impl<B: hal::Backend> FreeListAllocator<B> {
+    pub(crate) fn new(
+        id: hal::MemoryTypeId,
+        properties: hal::memory::Properties,
+        config: FreeListConfig,
+        max_chunk_size: Size,
+    ) -> Self {
+        FreeListAllocator {
+            id,
+            properties,
+            config,
+            max_chunk_size,
+            chunks: Vec::new(),
+        }
+    }
+
+    pub(crate) fn properties(&self) -> hal::memory::Properties {
+        self.properties
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.chunks.iter().all(Option::is_none)
+    }
+
+    pub(crate) fn try_alloc_existing(
+        &mut self,
+        size: Size,
+        align: Size,
+        name: Option<&str>,
+    ) -> Option<FreeListBlock<B>> {
+        let name: Option<Arc<str>> = name.map(Arc::from);
+        for (index, chunk) in self.chunks.iter_mut().enumerate() {
+            if let Some(chunk) = chunk {
+                if let Some((offset, region_offset, region_size)) = chunk.alloc(size, align, name.clone()) {
+                    return Some(FreeListBlock {
+                        chunk: index,
+                        memory: &chunk.memory,
+                        region_offset,
+                        region_size,
+                        offset,
+                        size,
+                        properties: self.properties,
+                        name,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Free every chunk, asserting none still has live blocks. Returns the
+    /// number of `hal::Memory` objects released back to the device.
+    pub(crate) fn clear(&mut self, device: &B::Device) -> usize {
+        let mut freed = 0;
+        for chunk in self.chunks.drain(..).flatten() {
+            assert!(chunk.is_entirely_free(), "free-list chunk dropped with live blocks");
+            unsafe { hal::device::Device::free_memory(device, chunk.memory) };
+            freed += 1;
+        }
+        freed
+    }
+
+    pub(crate) fn report(&self) -> Vec<ChunkReport> {
+        self.chunks
+            .iter()
+            .flatten()
+            .map(|chunk| ChunkReport {
+                size: chunk.size,
+                blocks: chunk
+                    .live
+                    .iter()
+                    .map(|block| BlockReport {
+                        offset: block.offset,
+                        size: block.size,
+                        name: block.name.as_deref().map(String::from),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+impl<B: hal::Backend> Allocator<B> for FreeListAllocator<B> {
+    type Block = FreeListBlock<B>;
+
+    fn alloc(
+        &mut self,
+        device: &B::Device,
+        size: Size,
+        align: Size,
+        name: Option<&str>,
+    ) -> Result<(Self::Block, Size), hal::device::AllocationError> {
+        if let Some(block) = self.try_alloc_existing(size, align, name) {
+            return Ok((block, 0));
+        }
+
+        let chunk_size = self.config.block_size.max(size).min(self.max_chunk_size).max(size);
+        // See `util::allocation_flags`: free-list chunks never carry device_address.
+        let flags = crate::util::allocation_flags(false);
+        let memory = unsafe { hal::device::Device::allocate_memory(device, self.id, chunk_size, flags)? };
+        let mut chunk = Box::new(FreeListChunk::new(memory, chunk_size));
+        let name: Option<Arc<str>> = name.map(Arc::from);
+        let (offset, region_offset, region_size) = chunk
+            .alloc(size, align, name.clone())
+            .expect("fresh chunk must satisfy its own size");
+        self.chunks.push(Some(chunk));
+
+        let memory = &self.chunks[self.chunks.len() - 1].as_ref().unwrap().memory;
+        Ok((
+            FreeListBlock {
+                chunk: self.chunks.len() - 1,
+                memory,
+                region_offset,
+                region_size,
+                offset,
+                size,
+                properties: self.properties,
+                name,
+            },
+            chunk_size,
+        ))
+    }
+
+    fn free(&mut self, device: &B::Device, block: Self::Block) -> Size {
+        let chunk = self.chunks[block.chunk]
+            .as_mut()
+            .expect("freeing a block from an already-released chunk");
+        chunk.free(block.offset, block.region_offset, block.region_size);
+
+        if !chunk.is_entirely_free() {
+            return 0;
+        }
+
+        let chunk = self.chunks[block.chunk].take().unwrap();
+        let freed = chunk.size;
+        unsafe { hal::device::Device::free_memory(device, chunk.memory) };
+        freed
+    }
+
+    fn report(&self) -> Vec<ChunkReport> {
+        FreeListAllocator::report(self)
+    }
+}
+
+impl<B: hal::Backend> std::fmt::Debug for FreeListAllocator<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FreeListAllocator")
+            .field("id", &self.id)
+            .field("config", &self.config)
+            .field("chunks", &self.chunks.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FreeRegions;
+
+    #[test]
+    fn alloc_splits_off_the_unused_remainder() {
+        let mut regions = FreeRegions::new(1024);
+        let (offset, region_offset, region_size) = regions.alloc(64, 1).unwrap();
+        assert_eq!((offset, region_offset, region_size), (0, 0, 64));
+        assert_eq!(regions.regions.len(), 1);
+        assert_eq!(regions.regions[0], super::FreeRegion { offset: 64, size: 960 });
+    }
+
+    #[test]
+    fn alloc_rounds_up_to_alignment_before_checking_fit() {
+        let mut regions = FreeRegions::new(128);
+        // Leaves a free region {offset: 100, size: 28}.
+        regions.alloc(100, 1).unwrap();
+        // Rounding offset 100 up to a 32-byte alignment lands at 128, which
+        // leaves no room for even a 1-byte request within the region ending
+        // at 128, even though the unaligned region itself isn't empty.
+        assert!(regions.alloc(1, 32).is_none());
+    }
+
+    #[test]
+    fn free_coalesces_with_both_neighbors() {
+        let mut regions = FreeRegions::new(300);
+        let (_, left_offset, left_size) = regions.alloc(100, 1).unwrap();
+        let (_, mid_offset, mid_size) = regions.alloc(100, 1).unwrap();
+        // One 100-byte free region remains at the end.
+        assert_eq!(regions.regions, vec![super::FreeRegion { offset: 200, size: 100 }]);
+
+        // The still-live middle block keeps the freed left block apart from
+        // the trailing free region.
+        regions.free(left_offset, left_size);
+        assert_eq!(
+            regions.regions,
+            vec![
+                super::FreeRegion { offset: 0, size: 100 },
+                super::FreeRegion { offset: 200, size: 100 },
+            ]
+        );
+
+        // Freeing the middle block merges it with the free regions on both sides.
+        regions.free(mid_offset, mid_size);
+        assert_eq!(regions.regions, vec![super::FreeRegion { offset: 0, size: 300 }]);
+    }
+
+    #[test]
+    fn free_without_adjacent_neighbors_does_not_merge() {
+        // Three 50-byte blocks carved from a 200-byte chunk, leaving 50 bytes
+        // free at the end.
+        let mut regions = FreeRegions::new(200);
+        let (_, first_offset, first_size) = regions.alloc(50, 1).unwrap();
+        let (_, _second_offset, _second_size) = regions.alloc(50, 1).unwrap();
+        let (_, third_offset, third_size) = regions.alloc(50, 1).unwrap();
+
+        // Free the first and third blocks, leaving the middle one live so
+        // they must NOT merge with each other across it.
+        regions.free(first_offset, first_size);
+        regions.free(third_offset, third_size);
+        assert_eq!(
+            regions.regions,
+            vec![
+                super::FreeRegion { offset: 0, size: 50 },
+                super::FreeRegion { offset: 100, size: 100 },
+            ]
+        );
+    }
+}
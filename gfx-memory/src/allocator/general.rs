@@ -0,0 +1,516 @@
+//! General-purpose (buddy) sub-allocator.
+//!
+//! Each chunk is split into power-of-two blocks. Allocation rounds the
+//! request up to the smallest order that fits, splitting a larger free block
+//! down when no free block of the exact order is available. Freeing a block
+//! walks back up, merging with its buddy whenever the buddy is also free, so
+//! long-lived chunks with mixed-size traffic don't fragment as badly as a
+//! pure bump allocator would.
+
+use std::sync::Arc;
+
+use super::Allocator;
+use crate::{
+    mapping::MappedRange,
+    report::{BlockReport, ChunkReport},
+    Size,
+};
+
+/// Size of the smallest (order-0) block handed out by the allocator.
+const MIN_BLOCK_SIZE: Size = 256;
+
+/// Smallest order whose `MIN_BLOCK_SIZE << order` covers `size` with at
+/// least `align` alignment. Free of any `hal::Backend` so it can be unit
+/// tested directly.
+fn order_for(size: Size, align: Size) -> u32 {
+    let size = size.max(align).max(MIN_BLOCK_SIZE);
+    let mut order = 0;
+    while (MIN_BLOCK_SIZE << order) < size {
+        order += 1;
+    }
+    order
+}
+
+/// Whether `MIN_BLOCK_SIZE << order` fits within `max_chunk_size`.
+fn order_fits_chunk(order: u32, max_chunk_size: Size) -> bool {
+    (MIN_BLOCK_SIZE << order) <= max_chunk_size
+}
+
+/// See `GeneralAllocator::chunk_size_for`. Free of any `hal::Backend` so it
+/// can be unit tested directly.
+fn chunk_size_for(min_order: u32, block_size: Size, max_chunk_size: Size) -> (Size, u32) {
+    let min_size = MIN_BLOCK_SIZE << min_order;
+    debug_assert!(min_size <= max_chunk_size);
+    let mut chunk_size = block_size.max(min_size).min(max_chunk_size).max(min_size);
+    let mut max_order = min_order;
+    while (MIN_BLOCK_SIZE << (max_order + 1)) <= chunk_size {
+        max_order += 1;
+    }
+    chunk_size = MIN_BLOCK_SIZE << max_order;
+    (chunk_size, max_order)
+}
+
+/// Config for the general-purpose sub-allocator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GeneralConfig {
+    /// Size of a chunk requested from the device. Rounded down to a power of
+    /// two multiple of `MIN_BLOCK_SIZE`, but never below the block being
+    /// requested.
+    pub block_size: Size,
+}
+
+/// Sub-block handed out by the [`GeneralAllocator`].
+///
+/// Chunks are boxed so their address is stable for the lifetime of any block
+/// carved out of them, even as the allocator's chunk list grows; `memory`
+/// points at the owning chunk's `B::Memory` for as long as this block lives.
+#[derive(Debug)]
+pub struct GeneralBlock<B: hal::Backend> {
+    chunk: usize,
+    memory: *const B::Memory,
+    offset: Size,
+    order: u32,
+    size: Size,
+    properties: hal::memory::Properties,
+    name: Option<Arc<str>>,
+    marker: std::marker::PhantomData<B>,
+}
+
+unsafe impl<B: hal::Backend> Send for GeneralBlock<B> {}
+unsafe impl<B: hal::Backend> Sync for GeneralBlock<B> {}
+
+impl<B: hal::Backend> GeneralBlock<B> {
+    pub(crate) fn size(&self) -> Size {
+        self.size
+    }
+
+    pub(crate) fn properties(&self) -> hal::memory::Properties {
+        self.properties
+    }
+
+    pub(crate) fn memory(&self) -> &B::Memory {
+        unsafe { &*self.memory }
+    }
+
+    pub(crate) fn segment(&self) -> hal::memory::Segment {
+        hal::memory::Segment {
+            offset: self.offset as _,
+            size: Some(self.size as _),
+        }
+    }
+
+    pub(crate) fn map<'a>(
+        &'a mut self,
+        device: &B::Device,
+        segment: hal::memory::Segment,
+    ) -> Result<MappedRange<'a, B>, hal::device::MapError> {
+        let memory = self.memory();
+        let ptr = unsafe { hal::device::Device::map_memory(device, memory, segment.clone())? };
+        Ok(MappedRange::new(
+            memory,
+            segment,
+            std::ptr::NonNull::new(ptr).expect("`map_memory` returned a null pointer"),
+        ))
+    }
+}
+
+/// A live sub-block tracked purely for `Heaps::report`.
+#[derive(Debug)]
+struct LiveBlock {
+    offset: Size,
+    size: Size,
+    name: Option<Arc<str>>,
+}
+
+/// Pure buddy bookkeeping over power-of-two block orders for one chunk,
+/// independent of the backing `hal::Memory` object so the splitting/merging
+/// logic can be unit tested without a `hal::Backend`.
+#[derive(Debug)]
+struct BuddyFreeLists {
+    max_order: u32,
+    // `free_lists[order]` holds the offsets of free blocks of that order.
+    free_lists: Vec<Vec<Size>>,
+}
+
+impl BuddyFreeLists {
+    fn new(max_order: u32) -> Self {
+        let mut free_lists = (0..=max_order).map(|_| Vec::new()).collect::<Vec<_>>();
+        free_lists[max_order as usize].push(0);
+        BuddyFreeLists { max_order, free_lists }
+    }
+
+    /// Find a free block of `order`, splitting the smallest larger free block
+    /// down if none of the exact order is available.
+    fn alloc(&mut self, order: u32) -> Option<Size> {
+        let order_index = order as usize;
+        if let Some(offset) = self.free_lists[order_index].pop() {
+            return Some(offset);
+        }
+
+        let mut larger = order_index + 1;
+        while larger <= self.max_order as usize && self.free_lists[larger].is_empty() {
+            larger += 1;
+        }
+        if larger > self.max_order as usize {
+            return None;
+        }
+
+        let offset = self.free_lists[larger].pop().unwrap();
+        for split_order in (order_index..larger).rev() {
+            let buddy = offset + (MIN_BLOCK_SIZE << split_order);
+            self.free_lists[split_order].push(buddy);
+        }
+        Some(offset)
+    }
+
+    /// Return a block to the free lists, merging with its buddy for as long
+    /// as the buddy is also free.
+    fn free(&mut self, mut offset: Size, mut order: u32) {
+        while (order as usize) < self.max_order as usize {
+            let buddy = offset ^ (MIN_BLOCK_SIZE << order);
+            let list = &mut self.free_lists[order as usize];
+            if let Some(pos) = list.iter().position(|&o| o == buddy) {
+                list.swap_remove(pos);
+                offset = offset.min(buddy);
+                order += 1;
+            } else {
+                break;
+            }
+        }
+        self.free_lists[order as usize].push(offset);
+    }
+
+    /// Whether every block has merged back into a single free block spanning
+    /// the whole chunk.
+    fn is_entirely_free(&self) -> bool {
+        self.free_lists[self.max_order as usize].len() == 1
+    }
+}
+
+#[derive(Debug)]
+struct GeneralChunk<B: hal::Backend> {
+    memory: B::Memory,
+    size: Size,
+    buddy: BuddyFreeLists,
+    live: Vec<LiveBlock>,
+}
+
+impl<B: hal::Backend> GeneralChunk<B> {
+    fn new(memory: B::Memory, size: Size, max_order: u32) -> Self {
+        GeneralChunk {
+            memory,
+            size,
+            buddy: BuddyFreeLists::new(max_order),
+            live: Vec::new(),
+        }
+    }
+
+    fn max_order(&self) -> u32 {
+        self.buddy.max_order
+    }
+
+    fn alloc(&mut self, order: u32, size: Size, name: Option<Arc<str>>) -> Option<Size> {
+        let offset = self.buddy.alloc(order)?;
+        self.live.push(LiveBlock { offset, size, name });
+        Some(offset)
+    }
+
+    fn free(&mut self, offset: Size, order: u32) {
+        self.live.retain(|live| live.offset != offset);
+        self.buddy.free(offset, order);
+    }
+
+    fn is_entirely_free(&self) -> bool {
+        self.live.is_empty()
+    }
+}
+
+/// Buddy sub-allocator used for long-lived, mixed-size resources.
+pub(crate) struct GeneralAllocator<B: hal::Backend> {
+    id: hal::MemoryTypeId,
+    properties: hal::memory::Properties,
+    config: GeneralConfig,
+    max_chunk_size: Size,
+    chunks: Vec<Option<Box<GeneralChunk<B>>>>,
+}
+
+impl<B: hal::Backend> GeneralAllocator<B> {
+    pub(crate) fn new(
+        id: hal::MemoryTypeId,
+        properties: hal::memory::Properties,
+        config: GeneralConfig,
+        max_chunk_size: Size,
+    ) -> Self {
+        GeneralAllocator {
+            id,
+            properties,
+            config,
+            max_chunk_size,
+            chunks: Vec::new(),
+        }
+    }
+
+    pub(crate) fn properties(&self) -> hal::memory::Properties {
+        self.properties
+    }
+
+    pub(crate) fn order_for(&self, size: Size, align: Size) -> u32 {
+        order_for(size, align)
+    }
+
+    /// Whether a chunk able to host a block of `order` stays within
+    /// `max_chunk_size`. `false` means the rounded-up power-of-two size for
+    /// this order alone exceeds the cap, so the buddy allocator can never
+    /// back it with any chunk; the caller must fall back to another
+    /// allocator or a dedicated allocation instead of calling `alloc`.
+    pub(crate) fn order_fits_chunk(&self, order: u32) -> bool {
+        order_fits_chunk(order, self.max_chunk_size)
+    }
+
+    /// Size of the backing chunk that would be requested from the device to
+    /// satisfy an allocation needing at least `min_order`, without performing
+    /// it. Never exceeds `max_chunk_size`, so a pool larger than that grows
+    /// by allocating several capped chunks instead of one oversized one.
+    ///
+    /// Precondition: `order_fits_chunk(min_order)`; otherwise `min_size`
+    /// itself exceeds `max_chunk_size` and the trailing `.max(min_size)` in
+    /// the free function `chunk_size_for` would blow through the cap.
+    pub(crate) fn chunk_size_for(&self, min_order: u32) -> (Size, u32) {
+        chunk_size_for(min_order, self.config.block_size, self.max_chunk_size)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.chunks.iter().all(Option::is_none)
+    }
+
+    /// Try to satisfy the request from an already-reserved chunk without
+    /// asking the device for a new `hal::Memory` object. Used when the
+    /// caller has run out of budget for new memory objects.
+    pub(crate) fn try_alloc_existing(
+        &mut self,
+        size: Size,
+        align: Size,
+        name: Option<&str>,
+    ) -> Option<GeneralBlock<B>> {
+        let order = self.order_for(size, align);
+        let name: Option<Arc<str>> = name.map(Arc::from);
+        for (index, chunk) in self.chunks.iter_mut().enumerate() {
+            if let Some(chunk) = chunk {
+                if order <= chunk.max_order() {
+                    if let Some(offset) = chunk.alloc(order, size, name.clone()) {
+                        return Some(GeneralBlock {
+                            chunk: index,
+                            memory: &chunk.memory,
+                            offset,
+                            order,
+                            size,
+                            properties: self.properties,
+                            name,
+                            marker: std::marker::PhantomData,
+                        });
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Free every chunk, asserting none still has live blocks. Returns the
+    /// number of `hal::Memory` objects released back to the device.
+    pub(crate) fn clear(&mut self, device: &B::Device) -> usize {
+        let mut freed = 0;
+        for chunk in self.chunks.drain(..).flatten() {
+            assert!(chunk.is_entirely_free(), "general chunk dropped with live blocks");
+            unsafe { hal::device::Device::free_memory(device, chunk.memory) };
+            freed += 1;
+        }
+        freed
+    }
+
+    pub(crate) fn report(&self) -> Vec<ChunkReport> {
+        self.chunks
+            .iter()
+            .flatten()
+            .map(|chunk| ChunkReport {
+                size: chunk.size,
+                blocks: chunk
+                    .live
+                    .iter()
+                    .map(|block| BlockReport {
+                        offset: block.offset,
+                        size: block.size,
+                        name: block.name.as_deref().map(String::from),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+impl<B: hal::Backend> Allocator<B> for GeneralAllocator<B> {
+    type Block = GeneralBlock<B>;
+
+    fn alloc(
+        &mut self,
+        device: &B::Device,
+        size: Size,
+        align: Size,
+        name: Option<&str>,
+    ) -> Result<(Self::Block, Size), hal::device::AllocationError> {
+        let order = self.order_for(size, align);
+        let name: Option<Arc<str>> = name.map(Arc::from);
+
+        for (index, chunk) in self.chunks.iter_mut().enumerate() {
+            if let Some(chunk) = chunk {
+                if order <= chunk.max_order() {
+                    if let Some(offset) = chunk.alloc(order, size, name.clone()) {
+                        return Ok((
+                            GeneralBlock {
+                                chunk: index,
+                                memory: &chunk.memory,
+                                offset,
+                                order,
+                                size,
+                                properties: self.properties,
+                                name,
+                                marker: std::marker::PhantomData,
+                            },
+                            0,
+                        ));
+                    }
+                }
+            }
+        }
+
+        let (chunk_size, max_order) = self.chunk_size_for(order);
+        // See `util::allocation_flags`: buddy chunks never carry device_address.
+        let flags = crate::util::allocation_flags(false);
+        let memory = unsafe { hal::device::Device::allocate_memory(device, self.id, chunk_size, flags)? };
+        let mut chunk = Box::new(GeneralChunk::new(memory, chunk_size, max_order));
+        let offset = chunk
+            .alloc(order, size, name.clone())
+            .expect("fresh chunk must satisfy its own order");
+        self.chunks.push(Some(chunk));
+
+        let memory = &self.chunks[self.chunks.len() - 1].as_ref().unwrap().memory;
+        Ok((
+            GeneralBlock {
+                chunk: self.chunks.len() - 1,
+                memory,
+                offset,
+                order,
+                size,
+                properties: self.properties,
+                name,
+                marker: std::marker::PhantomData,
+            },
+            chunk_size,
+        ))
+    }
+
+    fn free(&mut self, device: &B::Device, block: Self::Block) -> Size {
+        let chunk = self.chunks[block.chunk]
+            .as_mut()
+            .expect("freeing a block from an already-released chunk");
+        chunk.free(block.offset, block.order);
+        if !chunk.is_entirely_free() {
+            return 0;
+        }
+
+        let chunk = self.chunks[block.chunk].take().unwrap();
+        let freed = chunk.size;
+        unsafe { hal::device::Device::free_memory(device, chunk.memory) };
+        freed
+    }
+
+    fn report(&self) -> Vec<ChunkReport> {
+        GeneralAllocator::report(self)
+    }
+}
+
+impl<B: hal::Backend> std::fmt::Debug for GeneralAllocator<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GeneralAllocator")
+            .field("id", &self.id)
+            .field("config", &self.config)
+            .field("chunks", &self.chunks.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn order_fits_chunk_rejects_an_order_whose_rounded_size_exceeds_the_cap() {
+        // A 900-byte request rounds up to the order for 1024 bytes, which
+        // alone exceeds a 1000-byte cap even though 900 < 1000.
+        let order = order_for(900, 1);
+        assert_eq!(MIN_BLOCK_SIZE << order, 1024);
+        assert!(!order_fits_chunk(order, 1000));
+    }
+
+    #[test]
+    fn order_fits_chunk_accepts_an_order_within_the_cap() {
+        let order = order_for(256, 1);
+        assert!(order_fits_chunk(order, 1000));
+    }
+
+    #[test]
+    fn chunk_size_for_never_exceeds_the_cap_when_the_order_fits() {
+        let order = order_for(600, 1);
+        assert!(order_fits_chunk(order, 2048));
+        let (chunk_size, max_order) = chunk_size_for(order, 4096, 2048);
+        assert!(chunk_size <= 2048);
+        assert_eq!(chunk_size, MIN_BLOCK_SIZE << max_order);
+    }
+
+    #[test]
+    fn buddy_alloc_splits_a_larger_free_block_when_the_exact_order_is_empty() {
+        let mut buddy = BuddyFreeLists::new(3); // one free block spanning 8 * MIN_BLOCK_SIZE
+        let a = buddy.alloc(0).unwrap(); // splits all the way down to order 0
+        let b = buddy.alloc(0).unwrap();
+        assert_ne!(a, b);
+        assert_eq!(a.min(b), 0);
+        assert_eq!(a.max(b), MIN_BLOCK_SIZE);
+    }
+
+    #[test]
+    fn buddy_alloc_returns_none_once_every_order_is_exhausted() {
+        let mut buddy = BuddyFreeLists::new(0); // a single order-0 block, no splitting possible
+        assert!(buddy.alloc(0).is_some());
+        assert!(buddy.alloc(0).is_none());
+    }
+
+    #[test]
+    fn buddy_free_merges_buddies_back_into_the_original_block() {
+        let mut buddy = BuddyFreeLists::new(2); // one free block spanning 4 * MIN_BLOCK_SIZE
+        let a = buddy.alloc(0).unwrap();
+        let b = buddy.alloc(0).unwrap();
+        assert!(!buddy.is_entirely_free());
+
+        buddy.free(a, 0);
+        assert!(!buddy.is_entirely_free(), "buddy still live, must not merge yet");
+        buddy.free(b, 0);
+        assert!(buddy.is_entirely_free(), "both buddies free, should merge all the way up");
+    }
+
+    #[test]
+    fn buddy_free_does_not_merge_non_buddy_neighbors() {
+        // Order-2 chunk split into four order-0 blocks: offsets 0,256,512,768.
+        let mut buddy = BuddyFreeLists::new(2);
+        let offsets: Vec<Size> = (0..4).map(|_| buddy.alloc(0).unwrap()).collect();
+        assert!(!buddy.is_entirely_free());
+
+        // Free the two middle (non-buddy-paired) blocks: 256 is buddy of 0,
+        // and 768 is buddy of 512, so freeing 256 and 512 are buddies of
+        // *different* blocks and must not merge with each other.
+        let mid_low = *offsets.iter().find(|&&o| o == MIN_BLOCK_SIZE).unwrap();
+        let mid_high = *offsets.iter().find(|&&o| o == MIN_BLOCK_SIZE * 2).unwrap();
+        buddy.free(mid_low, 0);
+        buddy.free(mid_high, 0);
+        assert!(!buddy.is_entirely_free(), "order-0 blocks at 0 and 768 are still live");
+    }
+}
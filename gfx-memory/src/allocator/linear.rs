@@ -0,0 +1,324 @@
+//! Linear (bump) sub-allocator.
+//!
+//! Hands out memory from the end of the current chunk in order. A chunk
+//! cannot be reused until every block carved out of it has been freed, which
+//! makes this flavor cheap but only suitable for short-lived resources that
+//! are freed in roughly the order they were allocated (e.g. per-frame staging
+//! buffers).
+
+use std::sync::Arc;
+
+use super::Allocator;
+use crate::{
+    mapping::MappedRange,
+    report::{BlockReport, ChunkReport},
+    Size,
+};
+
+/// Config for the linear sub-allocator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinearConfig {
+    /// Size of a chunk requested from the device when the current one runs out of room.
+    pub linear_size: Size,
+}
+
+/// Sub-block handed out by the [`LinearAllocator`].
+///
+/// Chunks are boxed so their address is stable for the lifetime of any block
+/// carved out of them, even as the allocator's chunk list grows; `memory`
+/// points at the owning chunk's `B::Memory` for as long as this block lives.
+#[derive(Debug)]
+pub struct LinearBlock<B: hal::Backend> {
+    chunk: usize,
+    memory: *const B::Memory,
+    offset: Size,
+    size: Size,
+    properties: hal::memory::Properties,
+    name: Option<Arc<str>>,
+}
+
+unsafe impl<B: hal::Backend> Send for LinearBlock<B> {}
+unsafe impl<B: hal::Backend> Sync for LinearBlock<B> {}
+
+impl<B: hal::Backend> LinearBlock<B> {
+    pub(crate) fn size(&self) -> Size {
+        self.size
+    }
+
+    pub(crate) fn properties(&self) -> hal::memory::Properties {
+        self.properties
+    }
+
+    pub(crate) fn memory(&self) -> &B::Memory {
+        unsafe { &*self.memory }
+    }
+
+    pub(crate) fn segment(&self) -> hal::memory::Segment {
+        hal::memory::Segment {
+            offset: self.offset as _,
+            size: Some(self.size as _),
+        }
+    }
+
+    pub(crate) fn map<'a>(
+        &'a mut self,
+        device: &B::Device,
+        segment: hal::memory::Segment,
+    ) -> Result<MappedRange<'a, B>, hal::device::MapError> {
+        let memory = self.memory();
+        let ptr = unsafe { hal::device::Device::map_memory(device, memory, segment.clone())? };
+        Ok(MappedRange::new(
+            memory,
+            segment,
+            std::ptr::NonNull::new(ptr).expect("`map_memory` returned a null pointer"),
+        ))
+    }
+}
+
+/// A live sub-block tracked purely for `Heaps::report`.
+struct LiveBlock {
+    offset: Size,
+    size: Size,
+    name: Option<Arc<str>>,
+}
+
+struct LinearChunk<B: hal::Backend> {
+    memory: B::Memory,
+    size: Size,
+    bump: Size,
+    live: Vec<LiveBlock>,
+}
+
+/// Bump-allocates sub-blocks out of a small set of chunks.
+pub(crate) struct LinearAllocator<B: hal::Backend> {
+    id: hal::MemoryTypeId,
+    properties: hal::memory::Properties,
+    config: LinearConfig,
+    max_chunk_size: Size,
+    chunks: Vec<Option<Box<LinearChunk<B>>>>,
+}
+
+impl<B: hal::Backend> LinearAllocator<B> {
+    pub(crate) fn new(
+        id: hal::MemoryTypeId,
+        properties: hal::memory::Properties,
+        config: LinearConfig,
+        max_chunk_size: Size,
+    ) -> Self {
+        LinearAllocator {
+            id,
+            properties,
+            config,
+            max_chunk_size,
+            chunks: Vec::new(),
+        }
+    }
+
+    pub(crate) fn properties(&self) -> hal::memory::Properties {
+        self.properties
+    }
+
+    /// Size of the backing chunk that would be requested from the device to
+    /// satisfy an allocation of `size`, without actually performing it.
+    /// Never exceeds `max_chunk_size`; the caller is responsible for never
+    /// requesting a `size` larger than that cap in the first place.
+    pub(crate) fn chunk_size_for(&self, size: Size) -> Size {
+        self.config.linear_size.max(size).min(self.max_chunk_size).max(size)
+    }
+
+    fn alloc_chunk(
+        &mut self,
+        device: &B::Device,
+        size: Size,
+    ) -> Result<(usize, Size), hal::device::AllocationError> {
+        let chunk_size = self.chunk_size_for(size);
+        // See `util::allocation_flags`: linear chunks never carry device_address.
+        let flags = crate::util::allocation_flags(false);
+        let memory = unsafe { hal::device::Device::allocate_memory(device, self.id, chunk_size, flags)? };
+        self.chunks.push(Some(Box::new(LinearChunk {
+            memory,
+            size: chunk_size,
+            bump: 0,
+            live: Vec::new(),
+        })));
+        Ok((self.chunks.len() - 1, chunk_size))
+    }
+
+    /// Whether every chunk has drained and the allocator currently holds no
+    /// `hal::Memory` objects.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.chunks.iter().all(Option::is_none)
+    }
+
+    /// Try to satisfy the request from the current chunk without asking the
+    /// device for a new `hal::Memory` object. Used when the caller has run
+    /// out of budget for new memory objects.
+    pub(crate) fn try_alloc_existing(
+        &mut self,
+        size: Size,
+        align: Size,
+        name: Option<&str>,
+    ) -> Option<LinearBlock<B>> {
+        let (index, chunk) = self
+            .chunks
+            .iter_mut()
+            .enumerate()
+            .last()
+            .and_then(|(i, c)| c.as_mut().map(|c| (i, c)))?;
+
+        let offset = crate::util::align_up(chunk.bump, align);
+        if offset + size > chunk.size {
+            return None;
+        }
+
+        chunk.bump = offset + size;
+        let name: Option<Arc<str>> = name.map(Arc::from);
+        chunk.live.push(LiveBlock {
+            offset,
+            size,
+            name: name.clone(),
+        });
+        Some(LinearBlock {
+            chunk: index,
+            memory: &chunk.memory,
+            offset,
+            size,
+            properties: self.properties,
+            name,
+        })
+    }
+
+    /// Free every chunk, asserting none still has live blocks. Returns the
+    /// number of `hal::Memory` objects released back to the device.
+    pub(crate) fn clear(&mut self, device: &B::Device) -> usize {
+        let mut freed = 0;
+        for chunk in self.chunks.drain(..).flatten() {
+            assert!(chunk.live.is_empty(), "linear chunk dropped with live blocks");
+            unsafe { hal::device::Device::free_memory(device, chunk.memory) };
+            freed += 1;
+        }
+        freed
+    }
+
+    pub(crate) fn report(&self) -> Vec<ChunkReport> {
+        self.chunks
+            .iter()
+            .flatten()
+            .map(|chunk| ChunkReport {
+                size: chunk.size,
+                blocks: chunk
+                    .live
+                    .iter()
+                    .map(|block| BlockReport {
+                        offset: block.offset,
+                        size: block.size,
+                        name: block.name.as_deref().map(String::from),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+impl<B: hal::Backend> Allocator<B> for LinearAllocator<B> {
+    type Block = LinearBlock<B>;
+
+    fn alloc(
+        &mut self,
+        device: &B::Device,
+        size: Size,
+        align: Size,
+        name: Option<&str>,
+    ) -> Result<(Self::Block, Size), hal::device::AllocationError> {
+        let name: Option<Arc<str>> = name.map(Arc::from);
+
+        if let Some((index, chunk)) = self
+            .chunks
+            .iter_mut()
+            .enumerate()
+            .last()
+            .and_then(|(i, c)| c.as_mut().map(|c| (i, c)))
+        {
+            let offset = crate::util::align_up(chunk.bump, align);
+            if offset + size <= chunk.size {
+                chunk.bump = offset + size;
+                chunk.live.push(LiveBlock {
+                    offset,
+                    size,
+                    name: name.clone(),
+                });
+                return Ok((
+                    LinearBlock {
+                        chunk: index,
+                        memory: &chunk.memory,
+                        offset,
+                        size,
+                        properties: self.properties,
+                        name,
+                    },
+                    0,
+                ));
+            }
+        }
+
+        let (index, allocated) = self.alloc_chunk(device, size.max(align))?;
+        let chunk = self.chunks[index].as_mut().unwrap();
+        chunk.bump = size;
+        chunk.live.push(LiveBlock {
+            offset: 0,
+            size,
+            name: name.clone(),
+        });
+        Ok((
+            LinearBlock {
+                chunk: index,
+                memory: &chunk.memory,
+                offset: 0,
+                size,
+                properties: self.properties,
+                name,
+            },
+            allocated,
+        ))
+    }
+
+    fn free(&mut self, device: &B::Device, block: Self::Block) -> Size {
+        let chunk = self.chunks[block.chunk]
+            .as_mut()
+            .expect("freeing a block from an already-released chunk");
+        chunk.live.retain(|live| live.offset != block.offset);
+        if !chunk.live.is_empty() {
+            return 0;
+        }
+
+        let chunk = self.chunks[block.chunk].take().unwrap();
+        let freed = chunk.size;
+        unsafe { hal::device::Device::free_memory(device, chunk.memory) };
+        freed
+    }
+
+    fn report(&self) -> Vec<ChunkReport> {
+        LinearAllocator::report(self)
+    }
+}
+
+impl<B: hal::Backend> std::fmt::Debug for LinearAllocator<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LinearAllocator")
+            .field("id", &self.id)
+            .field("config", &self.config)
+            .field("chunks", &self.chunks.len())
+            .finish()
+    }
+}
+
+impl<B: hal::Backend> std::fmt::Debug for LinearChunk<B> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LinearChunk")
+            .field("size", &self.size)
+            .field("bump", &self.bump)
+            .field("live", &self.live.len())
+            .finish()
+    }
+}
@@ -0,0 +1,45 @@
+//! Sub-allocator flavors that `MemoryType` dispatches to.
+
+mod dedicated;
+mod free_list;
+mod general;
+mod linear;
+
+pub use self::{
+    dedicated::DedicatedBlock,
+    free_list::{FreeListBlock, FreeListConfig},
+    general::{GeneralBlock, GeneralConfig},
+    linear::{LinearBlock, LinearConfig},
+};
+
+pub(crate) use self::{free_list::FreeListAllocator, general::GeneralAllocator, linear::LinearAllocator};
+
+use crate::{report::ChunkReport, Size};
+
+/// Common interface implemented by every sub-allocator owned by a `MemoryType`.
+///
+/// `alloc`/`free` return the number of bytes actually requested from (or
+/// returned to) the device alongside the block, since sub-allocating into an
+/// already-reserved chunk costs nothing at the device level.
+pub(crate) trait Allocator<B: hal::Backend> {
+    /// Sub-block type produced by this allocator.
+    type Block;
+
+    /// Allocate a sub-block of `size` with at least `align` alignment,
+    /// optionally tagged with `name` for `Heaps::report`.
+    fn alloc(
+        &mut self,
+        device: &B::Device,
+        size: Size,
+        align: Size,
+        name: Option<&str>,
+    ) -> Result<(Self::Block, Size), hal::device::AllocationError>;
+
+    /// Free a sub-block previously returned by `alloc`. Returns the number of
+    /// bytes released back to the device, which is non-zero only when the
+    /// owning chunk became entirely free.
+    fn free(&mut self, device: &B::Device, block: Self::Block) -> Size;
+
+    /// Per-chunk breakdown of live sub-blocks, for `Heaps::report`.
+    fn report(&self) -> Vec<ChunkReport>;
+}
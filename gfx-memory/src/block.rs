@@ -0,0 +1,22 @@
+//! Trait implemented by every kind of memory block handed out by this crate.
+
+use crate::mapping::MappedRange;
+
+/// A block of device memory, regardless of which allocator produced it.
+pub trait Block<B: hal::Backend> {
+    /// Get memory properties of the block.
+    fn properties(&self) -> hal::memory::Properties;
+
+    /// Get memory object the block resides in.
+    fn memory(&self) -> &B::Memory;
+
+    /// Get memory segment occupied by the block.
+    fn segment(&self) -> hal::memory::Segment;
+
+    /// Map a region of the block's memory.
+    fn map<'a>(
+        &'a mut self,
+        device: &B::Device,
+        segment: hal::memory::Segment,
+    ) -> Result<MappedRange<'a, B>, hal::device::MapError>;
+}
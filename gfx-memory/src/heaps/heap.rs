@@ -0,0 +1,55 @@
+//! Tracks usage of a single `gfx-hal` memory heap.
+
+use crate::{report::MemoryHeapReport, stats::MemoryUtilization, Size};
+
+/// Usage accounting for one physical memory heap, shared by every memory
+/// type backed by it.
+#[derive(Debug)]
+pub(crate) struct MemoryHeap {
+    size: Size,
+    used: Size,
+    effective: Size,
+}
+
+impl MemoryHeap {
+    pub(crate) fn new(size: Size) -> Self {
+        MemoryHeap {
+            size,
+            used: 0,
+            effective: 0,
+        }
+    }
+
+    /// Bytes of this heap not yet reserved by any `hal::Memory` object.
+    pub(crate) fn available(&self) -> Size {
+        self.size - self.effective
+    }
+
+    /// Record that `allocated` bytes were reserved from the device and
+    /// `used` bytes of that reservation are claimed by the new block.
+    pub(crate) fn allocated(&mut self, allocated: Size, used: Size) {
+        self.effective += allocated;
+        self.used += used;
+    }
+
+    /// Record that `freed` bytes were released back to the device and
+    /// `used` bytes are no longer claimed by a block.
+    pub(crate) fn freed(&mut self, freed: Size, used: Size) {
+        self.effective -= freed;
+        self.used -= used;
+    }
+
+    pub(crate) fn utilization(&self) -> MemoryUtilization {
+        MemoryUtilization {
+            used: self.used,
+            effective: self.effective,
+        }
+    }
+
+    pub(crate) fn report(&self) -> MemoryHeapReport {
+        MemoryHeapReport {
+            total: self.size,
+            used: self.effective,
+        }
+    }
+}
@@ -0,0 +1,488 @@
+//! Dispatches allocation requests for a single `gfx-hal` memory type to one
+//! of its configured sub-allocators, or straight to the device for a
+//! dedicated allocation.
+
+use std::sync::Arc;
+
+use super::{BlockFlavor, Dedicated, HeapsConfig, HeapsError};
+use crate::{
+    allocator::{Allocator, DedicatedBlock, FreeListAllocator, GeneralAllocator, LinearAllocator},
+    report::{BlockReport, ChunkReport, MemoryTypeReport},
+    stats::MemoryTypeUtilization,
+    usage::MemoryUsage,
+    Size,
+};
+
+/// Below the dedicated threshold, `Dedicated::Preferred` only takes the
+/// dedicated path when the owning heap has at least this many multiples of
+/// the request still available;
+/// otherwise a single large-but-affordable allocation could eat into room
+/// other requests need.
+const PREFERRED_DEDICATED_HEADROOM: Size = 4;
+
+/// Whether a request should get a dedicated `hal::Memory` object for the
+/// given `dedicated` hint, request `size`, the owning heap's free bytes
+/// (`heap_available`) and the applicable `threshold` (already resolved for
+/// transient vs. non-transient usage). Free of `hal::Backend` so it can be
+/// unit tested directly.
+fn wants_dedicated(dedicated: Dedicated, size: Size, heap_available: Size, threshold: Size) -> bool {
+    match dedicated {
+        Dedicated::Required => true,
+        Dedicated::Preferred => {
+            size >= threshold || heap_available >= size.saturating_mul(PREFERRED_DEDICATED_HEADROOM)
+        }
+        Dedicated::Indifferent => size >= threshold,
+    }
+}
+
+/// What `MemoryType::alloc` must do before considering sub-allocation, given
+/// the `device_address`/`dedicated` hints and whether the budget for new
+/// `hal::Memory` objects is exhausted. Free of `hal::Backend` so the dispatch
+/// can be unit tested directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DedicatedRequirement {
+    /// Neither hint forces a dedicated allocation; continue normal dispatch.
+    None,
+    /// A dedicated allocation is required, with this `device_address` flag,
+    /// and the budget allows requesting one.
+    Required { device_address: bool },
+    /// A dedicated allocation is required but the budget for new
+    /// `hal::Memory` objects is exhausted.
+    Denied,
+}
+
+fn dedicated_requirement(
+    device_address: bool,
+    dedicated: Dedicated,
+    allow_new_allocation: bool,
+) -> DedicatedRequirement {
+    if device_address {
+        return if allow_new_allocation {
+            DedicatedRequirement::Required { device_address: true }
+        } else {
+            DedicatedRequirement::Denied
+        };
+    }
+
+    if dedicated == Dedicated::Required {
+        return if allow_new_allocation {
+            DedicatedRequirement::Required { device_address: false }
+        } else {
+            DedicatedRequirement::Denied
+        };
+    }
+
+    DedicatedRequirement::None
+}
+
+/// Build the `ChunkReport`s describing every dedicated allocation, each
+/// reported as a single whole-chunk block. Free of `hal::Backend` so it can
+/// be unit tested directly.
+fn dedicated_report(dedicated: &[Option<(Size, Option<Arc<str>>)>]) -> Vec<ChunkReport> {
+    dedicated
+        .iter()
+        .flatten()
+        .map(|(size, name)| ChunkReport {
+            size: *size,
+            blocks: vec![BlockReport {
+                offset: 0,
+                size: *size,
+                name: name.as_deref().map(String::from),
+            }],
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub(crate) struct MemoryType<B: hal::Backend> {
+    id: hal::MemoryTypeId,
+    heap_index: usize,
+    properties: hal::memory::Properties,
+    config: HeapsConfig,
+    linear: Option<LinearAllocator<B>>,
+    general: Option<GeneralAllocator<B>>,
+    free_list: Option<FreeListAllocator<B>>,
+    // Slab of live dedicated allocations, indexed by `DedicatedBlock::slot`,
+    // kept only so `report` can describe them; `None` marks a freed slot
+    // available for reuse.
+    dedicated: Vec<Option<(Size, Option<Arc<str>>)>>,
+}
+
+impl<B: hal::Backend> MemoryType<B> {
+    pub(crate) fn new(
+        id: hal::MemoryTypeId,
+        heap_index: usize,
+        properties: hal::memory::Properties,
+        config: HeapsConfig,
+        _non_coherent_atom_size: Size,
+        max_memory_allocation_size: Size,
+    ) -> Self {
+        MemoryType {
+            id,
+            heap_index,
+            properties,
+            linear: config
+                .linear
+                .map(|c| LinearAllocator::new(id, properties, c, max_memory_allocation_size)),
+            general: config
+                .general
+                .map(|c| GeneralAllocator::new(id, properties, c, max_memory_allocation_size)),
+            free_list: config
+                .free_list
+                .map(|c| FreeListAllocator::new(id, properties, c, max_memory_allocation_size)),
+            config,
+            dedicated: Vec::new(),
+        }
+    }
+
+    pub(crate) fn properties(&self) -> hal::memory::Properties {
+        self.properties
+    }
+
+    pub(crate) fn heap_index(&self) -> usize {
+        self.heap_index
+    }
+
+    /// Whether this memory type should get a dedicated `hal::Memory` object
+    /// for a request of `size` with the given `dedicated` hint. `heap_available`
+    /// is the owning heap's free bytes before this request is satisfied.
+    fn wants_dedicated(&self, usage: MemoryUsage, dedicated: Dedicated, size: Size, heap_available: Size) -> bool {
+        let threshold = if usage.is_transient() {
+            self.config.transient_dedicated_threshold
+        } else {
+            self.config.dedicated_threshold
+        };
+
+        wants_dedicated(dedicated, size, heap_available, threshold)
+    }
+
+    /// Allocate a block. `allow_new_allocation` is `false` once `Heaps` has
+    /// run out of budget for new `hal::Memory` objects: in that case this
+    /// never asks the device for a new chunk or dedicated allocation, and
+    /// fails with `HeapsError::TooManyObjects` if nothing already reserved
+    /// can satisfy the request.
+    ///
+    /// `device_address` forces a dedicated allocation regardless of
+    /// `dedicated` or the configured thresholds, since device-address memory
+    /// cannot always be sub-allocated as freely; an existing chunk can never
+    /// serve such a request, so `allow_new_allocation == false` fails it
+    /// outright rather than trying `alloc_existing`.
+    ///
+    /// `Dedicated::Required` carries the same "must not share an allocation"
+    /// guarantee: it is checked, and fails outright on a depleted budget,
+    /// before the budget short-circuit ever gets a chance to quietly resolve
+    /// it to a sub-allocated block instead.
+    ///
+    /// `heap_available` is the owning heap's free bytes before this request,
+    /// used to judge whether `Dedicated::Preferred` has room to spare.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn alloc(
+        &mut self,
+        device: &B::Device,
+        usage: MemoryUsage,
+        dedicated: Dedicated,
+        size: Size,
+        align: Size,
+        allow_new_allocation: bool,
+        name: Option<&str>,
+        device_address: bool,
+        heap_available: Size,
+    ) -> Result<(BlockFlavor<B>, Size), HeapsError> {
+        match dedicated_requirement(device_address, dedicated, allow_new_allocation) {
+            DedicatedRequirement::Required { device_address } => {
+                return self.alloc_dedicated(device, size, name, device_address)
+            }
+            DedicatedRequirement::Denied => return Err(HeapsError::TooManyObjects),
+            DedicatedRequirement::None => {}
+        }
+
+        if !allow_new_allocation {
+            return self
+                .alloc_existing(usage, size, align, name)
+                .ok_or(HeapsError::TooManyObjects);
+        }
+
+        if self.wants_dedicated(usage, dedicated, size, heap_available) {
+            return self.alloc_dedicated(device, size, name, false);
+        }
+
+        if usage.is_transient() {
+            if let Some(linear) = &mut self.linear {
+                let (block, allocated) = linear.alloc(device, size, align, name)?;
+                return Ok((BlockFlavor::Linear(block), allocated));
+            }
+        }
+
+        if usage.prefers_free_list() {
+            if let Some(free_list) = &mut self.free_list {
+                let (block, allocated) = free_list.alloc(device, size, align, name)?;
+                return Ok((BlockFlavor::FreeList(block), allocated));
+            }
+        }
+
+        if let Some(general) = &mut self.general {
+            if general.order_fits_chunk(general.order_for(size, align)) {
+                let (block, allocated) = general.alloc(device, size, align, name)?;
+                return Ok((BlockFlavor::General(block), allocated));
+            }
+        }
+
+        if let Some(free_list) = &mut self.free_list {
+            let (block, allocated) = free_list.alloc(device, size, align, name)?;
+            return Ok((BlockFlavor::FreeList(block), allocated));
+        }
+
+        self.alloc_dedicated(device, size, name, false)
+    }
+
+    /// Try to satisfy a request purely from chunks already reserved from the
+    /// device, without creating a new `hal::Memory` object of any kind.
+    fn alloc_existing(
+        &mut self,
+        usage: MemoryUsage,
+        size: Size,
+        align: Size,
+        name: Option<&str>,
+    ) -> Option<(BlockFlavor<B>, Size)> {
+        if usage.is_transient() {
+            if let Some(block) = self
+                .linear
+                .as_mut()
+                .and_then(|l| l.try_alloc_existing(size, align, name))
+            {
+                return Some((BlockFlavor::Linear(block), 0));
+            }
+        }
+
+        if let Some(block) = self
+            .general
+            .as_mut()
+            .and_then(|g| g.try_alloc_existing(size, align, name))
+        {
+            return Some((BlockFlavor::General(block), 0));
+        }
+
+        if let Some(block) = self
+            .free_list
+            .as_mut()
+            .and_then(|f| f.try_alloc_existing(size, align, name))
+        {
+            return Some((BlockFlavor::FreeList(block), 0));
+        }
+
+        None
+    }
+
+    fn alloc_dedicated(
+        &mut self,
+        device: &B::Device,
+        size: Size,
+        name: Option<&str>,
+        device_address: bool,
+    ) -> Result<(BlockFlavor<B>, Size), HeapsError> {
+        let flags = crate::util::allocation_flags(device_address);
+        let memory = unsafe { hal::device::Device::allocate_memory(device, self.id, size, flags)? };
+        let name: Option<Arc<str>> = name.map(Arc::from);
+        let slot = match self.dedicated.iter().position(Option::is_none) {
+            Some(slot) => {
+                self.dedicated[slot] = Some((size, name.clone()));
+                slot
+            }
+            None => {
+                self.dedicated.push(Some((size, name.clone())));
+                self.dedicated.len() - 1
+            }
+        };
+        Ok((
+            BlockFlavor::Dedicated(DedicatedBlock::new(
+                memory,
+                self.properties,
+                size,
+                name,
+                slot,
+                device_address,
+            )),
+            size,
+        ))
+    }
+
+    pub(crate) fn free(&mut self, device: &B::Device, block: BlockFlavor<B>) -> Size {
+        match block {
+            BlockFlavor::Dedicated(block) => {
+                let size = block.size();
+                let slot = block.slot();
+                self.dedicated[slot] = None;
+                unsafe { hal::device::Device::free_memory(device, block.unwrap_memory()) };
+                size
+            }
+            BlockFlavor::Linear(block) => self
+                .linear
+                .as_mut()
+                .expect("linear block freed but type has no linear allocator")
+                .free(device, block),
+            BlockFlavor::General(block) => self
+                .general
+                .as_mut()
+                .expect("general block freed but type has no general allocator")
+                .free(device, block),
+            BlockFlavor::FreeList(block) => self
+                .free_list
+                .as_mut()
+                .expect("free-list block freed but type has no free-list allocator")
+                .free(device, block),
+        }
+    }
+
+    /// Free every chunk owned by this memory type's sub-allocators. Returns
+    /// the number of `hal::Memory` objects released back to the device.
+    ///
+    /// Dedicated allocations aren't owned by this registry (the `hal::Memory`
+    /// lives in the caller's `DedicatedBlock`, freed via `MemoryType::free`),
+    /// so there's nothing here to free on their behalf; this only asserts
+    /// none are still outstanding, same as the sub-allocators assert none of
+    /// their chunks have live blocks.
+    pub(crate) fn clear(&mut self, device: &B::Device) -> u32 {
+        let mut freed = 0;
+        if let Some(linear) = &mut self.linear {
+            freed += linear.clear(device) as u32;
+        }
+        if let Some(general) = &mut self.general {
+            freed += general.clear(device) as u32;
+        }
+        if let Some(free_list) = &mut self.free_list {
+            freed += free_list.clear(device) as u32;
+        }
+        assert!(
+            self.dedicated.iter().all(Option::is_none),
+            "memory type cleared with live dedicated allocations"
+        );
+        freed
+    }
+
+    pub(crate) fn utilization(&self) -> MemoryTypeUtilization {
+        // Walk the same chunk/block data `report` does: `effective` is every
+        // chunk's (and dedicated allocation's) size, `used` is the live
+        // sub-blocks actually claimed out of them.
+        self.report()
+            .chunks
+            .iter()
+            .fold(MemoryTypeUtilization::default(), |acc, chunk| {
+                MemoryTypeUtilization {
+                    used: acc.used + chunk.blocks.iter().map(|block| block.size).sum::<Size>(),
+                    effective: acc.effective + chunk.size,
+                }
+            })
+    }
+
+    pub(crate) fn report(&self) -> MemoryTypeReport {
+        let mut chunks = Vec::new();
+        if let Some(linear) = &self.linear {
+            chunks.extend(linear.report());
+        }
+        if let Some(general) = &self.general {
+            chunks.extend(general.report());
+        }
+        if let Some(free_list) = &self.free_list {
+            chunks.extend(free_list.report());
+        }
+        chunks.extend(dedicated_report(&self.dedicated));
+        MemoryTypeReport { chunks }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wants_dedicated_required_ignores_size_and_heap_room() {
+        assert!(wants_dedicated(Dedicated::Required, 1, 0, Size::MAX));
+    }
+
+    #[test]
+    fn wants_dedicated_preferred_only_at_or_above_the_heap_room_headroom() {
+        let size = 1024;
+        // Exactly `PREFERRED_DEDICATED_HEADROOM` multiples of `size` free: takes the dedicated path.
+        assert!(wants_dedicated(
+            Dedicated::Preferred,
+            size,
+            size * PREFERRED_DEDICATED_HEADROOM,
+            Size::MAX,
+        ));
+        // One byte short of that headroom: stays sub-allocated.
+        assert!(!wants_dedicated(
+            Dedicated::Preferred,
+            size,
+            size * PREFERRED_DEDICATED_HEADROOM - 1,
+            Size::MAX,
+        ));
+    }
+
+    #[test]
+    fn wants_dedicated_indifferent_compares_against_threshold() {
+        assert!(wants_dedicated(Dedicated::Indifferent, 100, Size::MAX, 100));
+        assert!(!wants_dedicated(Dedicated::Indifferent, 99, Size::MAX, 100));
+    }
+
+    #[test]
+    fn dedicated_requirement_device_address_forces_dedicated_when_budget_allows() {
+        assert_eq!(
+            dedicated_requirement(true, Dedicated::Indifferent, true),
+            DedicatedRequirement::Required { device_address: true },
+        );
+    }
+
+    #[test]
+    fn dedicated_requirement_device_address_denied_once_budget_is_exhausted() {
+        assert_eq!(
+            dedicated_requirement(true, Dedicated::Indifferent, false),
+            DedicatedRequirement::Denied,
+        );
+    }
+
+    #[test]
+    fn dedicated_requirement_required_forces_dedicated_when_budget_allows() {
+        assert_eq!(
+            dedicated_requirement(false, Dedicated::Required, true),
+            DedicatedRequirement::Required { device_address: false },
+        );
+    }
+
+    #[test]
+    fn dedicated_requirement_required_denied_once_budget_is_exhausted() {
+        assert_eq!(
+            dedicated_requirement(false, Dedicated::Required, false),
+            DedicatedRequirement::Denied,
+        );
+    }
+
+    #[test]
+    fn dedicated_requirement_falls_through_for_preferred_and_indifferent() {
+        assert_eq!(
+            dedicated_requirement(false, Dedicated::Preferred, true),
+            DedicatedRequirement::None,
+        );
+        assert_eq!(
+            dedicated_requirement(false, Dedicated::Indifferent, false),
+            DedicatedRequirement::None,
+        );
+    }
+
+    #[test]
+    fn dedicated_report_describes_each_live_slot_as_a_whole_chunk_block() {
+        let dedicated = vec![
+            Some((1024, Some(Arc::from("vertex buffer")))),
+            None, // a freed slot must not show up in the report
+            Some((256, None)),
+        ];
+        let chunks = dedicated_report(&dedicated);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].size, 1024);
+        assert_eq!(chunks[0].blocks.len(), 1);
+        assert_eq!(chunks[0].blocks[0].offset, 0);
+        assert_eq!(chunks[0].blocks[0].size, 1024);
+        assert_eq!(chunks[0].blocks[0].name.as_deref(), Some("vertex buffer"));
+        assert_eq!(chunks[1].size, 256);
+        assert_eq!(chunks[1].blocks[0].name, None);
+    }
+}
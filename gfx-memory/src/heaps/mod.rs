@@ -1,10 +1,12 @@
 mod heap;
 mod memory_type;
 
+use std::sync::Arc;
+
 use self::{heap::MemoryHeap, memory_type::MemoryType};
 use crate::{
-    allocator::*, block::Block, mapping::MappedRange, stats::TotalMemoryUtilization,
-    usage::MemoryUsage, Size,
+    allocator::*, block::Block, mapping::MappedRange, report::MemoryReport,
+    stats::TotalMemoryUtilization, usage::MemoryUsage, Size,
 };
 
 /// Possible errors returned by `Heaps`.
@@ -14,6 +16,15 @@ pub enum HeapsError {
     AllocationError(hal::device::AllocationError),
     /// No memory types among required for resource with requested properties was found.
     NoSuitableMemory(u32, hal::memory::Properties),
+    /// The device's `maxMemoryAllocationCount` budget is exhausted and the
+    /// request could not be satisfied by reusing an already-reserved chunk.
+    TooManyObjects,
+    /// The requested size exceeds `Heaps`'s configured
+    /// `max_memory_allocation_size`. Carries `(requested size, the cap)`.
+    TooLarge(Size, Size),
+    /// A block was requested with the `device_address` flag set, but
+    /// `Heaps` was not constructed with `buffer_device_address` support.
+    DeviceAddressUnsupported,
 }
 
 impl std::fmt::Display for HeapsError {
@@ -25,6 +36,19 @@ impl std::fmt::Display for HeapsError {
                 "Memory type among ({}) with properties ({:?}) not found",
                 e, e2
             ),
+            HeapsError::TooManyObjects => write!(
+                f,
+                "Reached the limit on the number of live memory objects"
+            ),
+            HeapsError::TooLarge(size, max) => write!(
+                f,
+                "Requested size ({}) exceeds the maximum memory allocation size ({})",
+                size, max
+            ),
+            HeapsError::DeviceAddressUnsupported => write!(
+                f,
+                "Block requested with `device_address` set, but `Heaps` was built without buffer_device_address support"
+            ),
         }
     }
 }
@@ -32,11 +56,22 @@ impl std::error::Error for HeapsError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match *self {
             HeapsError::AllocationError(ref err) => Some(err),
-            HeapsError::NoSuitableMemory(..) => None,
+            HeapsError::NoSuitableMemory(..)
+            | HeapsError::TooManyObjects
+            | HeapsError::TooLarge(..)
+            | HeapsError::DeviceAddressUnsupported => None,
         }
     }
 }
 
+/// Whether a `device_address` request should be let through to
+/// `MemoryType::alloc`, given whether `Heaps` was built with
+/// `buffer_device_address` support. Free of `hal::Backend` so it can be unit
+/// tested directly.
+fn device_address_allowed(device_address: bool, buffer_device_address: bool) -> bool {
+    !device_address || buffer_device_address
+}
+
 impl From<hal::device::AllocationError> for HeapsError {
     fn from(error: hal::device::AllocationError) -> Self {
         HeapsError::AllocationError(error)
@@ -58,6 +93,33 @@ pub struct HeapsConfig {
 
     /// Config for general sub-allocator.
     pub general: Option<GeneralConfig>,
+
+    /// Config for the free-list sub-allocator.
+    pub free_list: Option<FreeListConfig>,
+
+    /// Requests at or above this size always get a dedicated `hal::Memory`
+    /// object, regardless of the `Dedicated` hint passed to `allocate`.
+    pub dedicated_threshold: Size,
+
+    /// Like `dedicated_threshold` but applied to requests for transient
+    /// usages (see `MemoryUsage::is_transient`). Transient resources are
+    /// cheap to rebuild, so this is typically set lower than
+    /// `dedicated_threshold` to keep them out of the linear sub-allocator.
+    pub transient_dedicated_threshold: Size,
+}
+
+/// Hint controlling whether an allocation should bypass sub-allocation and
+/// get a standalone `hal::Memory` object of its own.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dedicated {
+    /// Always allocate a dedicated `hal::Memory` object for this resource,
+    /// e.g. for externally imported or exported memory.
+    Required,
+    /// Prefer a dedicated allocation when the owning heap has ample room,
+    /// but allow falling back to sub-allocation otherwise.
+    Preferred,
+    /// Let `Heaps` decide based on `size` and the configured thresholds.
+    Indifferent,
 }
 
 /// Heaps available on particular physical device.
@@ -65,11 +127,35 @@ pub struct HeapsConfig {
 pub struct Heaps<B: hal::Backend> {
     types: Vec<MemoryType<B>>,
     heaps: Vec<MemoryHeap>,
+    allocations_remains: u32,
+    max_memory_allocation_size: Size,
+    buffer_device_address: bool,
 }
 
 impl<B: hal::Backend> Heaps<B> {
     /// This must be called with `hal::memory::Properties` fetched from physical device.
-    pub unsafe fn new<P, H>(types: P, heaps: H, non_coherent_atom_size: Size) -> Self
+    ///
+    /// `max_memory_allocation_count` should come from the device's
+    /// `maxMemoryAllocationCount` limit; `Heaps` uses it to avoid exceeding
+    /// the number of live `hal::Memory` objects the driver allows.
+    ///
+    /// `max_memory_allocation_size` should come from the device's
+    /// `maxMemoryAllocationSize` limit (or an equivalent driver cap); no
+    /// single `hal::Memory` object `Heaps` creates, whether dedicated or a
+    /// sub-allocator chunk, will ever exceed it.
+    ///
+    /// `buffer_device_address` should be `true` when the device was created
+    /// with `VK_KHR_buffer_device_address` (or `shaderDeviceAddress`)
+    /// enabled; it gates the per-allocation `device_address` flag on
+    /// `allocate`.
+    pub unsafe fn new<P, H>(
+        types: P,
+        heaps: H,
+        non_coherent_atom_size: Size,
+        max_memory_allocation_count: u32,
+        max_memory_allocation_size: Size,
+        buffer_device_address: bool,
+    ) -> Self
     where
         P: IntoIterator<Item = (hal::memory::Properties, u32, HeapsConfig)>,
         H: IntoIterator<Item = Size>,
@@ -89,10 +175,14 @@ impl<B: hal::Backend> Heaps<B> {
                         properties,
                         config,
                         non_coherent_atom_size,
+                        max_memory_allocation_size,
                     )
                 })
                 .collect(),
             heaps,
+            allocations_remains: max_memory_allocation_count,
+            max_memory_allocation_size,
+            buffer_device_address,
         }
     }
 
@@ -101,13 +191,31 @@ impl<B: hal::Backend> Heaps<B> {
     /// for intended `usage`,
     /// with `size`
     /// and `align` requirements.
+    ///
+    /// `dedicated` hints whether the block should be backed by a standalone
+    /// `hal::Memory` object instead of being sub-allocated; see `Dedicated`.
+    ///
+    /// `name`, if given, is attached to the block and shows up against it in
+    /// `Heaps::report`.
+    ///
+    /// `device_address` requests that the backing `hal::Memory` object be
+    /// allocated with the device-address allocation flag, for a buffer that
+    /// will be bound with `VK_BUFFER_USAGE_SHADER_DEVICE_ADDRESS_BIT`. Such
+    /// memory cannot be shared with sub-allocated blocks that don't need it,
+    /// so a `true` request always gets a dedicated `hal::Memory` object
+    /// regardless of `dedicated`. Fails with `HeapsError::DeviceAddressUnsupported`
+    /// unless `Heaps` was built with `buffer_device_address` support.
+    #[allow(clippy::too_many_arguments)]
     pub fn allocate(
         &mut self,
         device: &B::Device,
         mask: u32,
         usage: MemoryUsage,
+        dedicated: Dedicated,
         size: Size,
         align: Size,
+        name: Option<&str>,
+        device_address: bool,
     ) -> Result<MemoryBlock<B>, HeapsError> {
         let (memory_index, _, _) = {
             let suitable_types = self
@@ -140,7 +248,16 @@ impl<B: hal::Backend> Heaps<B> {
                 })?
         };
 
-        self.allocate_from(device, memory_index as u32, usage, size, align)
+        self.allocate_from(
+            device,
+            memory_index as u32,
+            usage,
+            dedicated,
+            size,
+            align,
+            name,
+            device_address,
+        )
     }
 
     /// Allocate memory block
@@ -148,35 +265,64 @@ impl<B: hal::Backend> Heaps<B> {
     /// for intended `usage`,
     /// with `size`
     /// and `align` requirements.
+    #[allow(clippy::too_many_arguments)]
     fn allocate_from(
         &mut self,
         device: &B::Device,
         memory_index: u32,
         usage: MemoryUsage,
+        dedicated: Dedicated,
         size: Size,
         align: Size,
+        name: Option<&str>,
+        device_address: bool,
     ) -> Result<MemoryBlock<B>, HeapsError> {
         log::trace!(
-            "Allocate memory block: type '{}', usage '{:#?}', size: '{}', align: '{}'",
+            "Allocate memory block: type '{}', usage '{:#?}', dedicated '{:?}', size: '{}', align: '{}'",
             memory_index,
             usage,
+            dedicated,
             size,
             align
         );
 
+        if size > self.max_memory_allocation_size {
+            return Err(HeapsError::TooLarge(size, self.max_memory_allocation_size));
+        }
+
+        if !device_address_allowed(device_address, self.buffer_device_address) {
+            return Err(HeapsError::DeviceAddressUnsupported);
+        }
+
         let ref mut memory_type = self.types[memory_index as usize];
         let ref mut memory_heap = self.heaps[memory_type.heap_index()];
 
-        if memory_heap.available() < size {
+        let heap_available = memory_heap.available();
+        if heap_available < size {
             return Err(hal::device::OutOfMemory::Device.into());
         }
 
-        let (flavor, allocated) = memory_type.alloc(device, usage, size, align)?;
+        let allow_new_allocation = self.allocations_remains > 0;
+        let (flavor, allocated) = memory_type.alloc(
+            device,
+            usage,
+            dedicated,
+            size,
+            align,
+            allow_new_allocation,
+            name,
+            device_address,
+            heap_available,
+        )?;
+        if allocated > 0 {
+            self.allocations_remains -= 1;
+        }
         memory_heap.allocated(allocated, flavor.size());
 
         Ok(MemoryBlock {
             flavor,
             memory_index,
+            name: name.map(Arc::from),
         })
     }
 
@@ -191,6 +337,9 @@ impl<B: hal::Backend> Heaps<B> {
         let ref mut memory_type = self.types[memory_index as usize];
         let ref mut memory_heap = self.heaps[memory_type.heap_index()];
         let freed = memory_type.free(device, block.flavor);
+        if freed > 0 {
+            self.allocations_remains += 1;
+        }
         memory_heap.freed(freed, size);
     }
 
@@ -198,7 +347,7 @@ impl<B: hal::Backend> Heaps<B> {
     /// Will panic if memory instances are left allocated.
     pub fn clear(&mut self, device: &B::Device) {
         for mut mt in self.types.drain(..) {
-            mt.clear(device)
+            self.allocations_remains += mt.clear(device);
         }
     }
 
@@ -207,6 +356,17 @@ impl<B: hal::Backend> Heaps<B> {
         TotalMemoryUtilization {
             heaps: self.heaps.iter().map(MemoryHeap::utilization).collect(),
             types: self.types.iter().map(MemoryType::utilization).collect(),
+            allocations_remains: self.allocations_remains,
+        }
+    }
+
+    /// Build a structured snapshot of every live allocation, down to
+    /// individual named sub-blocks. Unlike `utilization`, this is detailed
+    /// enough to diff between frames and track down what is growing.
+    pub fn report(&self) -> MemoryReport {
+        MemoryReport {
+            heaps: self.heaps.iter().map(MemoryHeap::report).collect(),
+            types: self.types.iter().map(MemoryType::report).collect(),
         }
     }
 }
@@ -224,6 +384,7 @@ impl<B: hal::Backend> Drop for Heaps<B> {
 pub struct MemoryBlock<B: hal::Backend> {
     flavor: BlockFlavor<B>,
     memory_index: u32,
+    name: Option<Arc<str>>,
 }
 
 impl<B: hal::Backend> MemoryBlock<B> {
@@ -231,13 +392,29 @@ impl<B: hal::Backend> MemoryBlock<B> {
     pub fn memory_type(&self) -> u32 {
         self.memory_index
     }
+
+    /// Name passed to `Heaps::allocate`, if any.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Whether the backing `hal::Memory` object was allocated with the
+    /// device-address allocation flag; see `Heaps::allocate`'s
+    /// `device_address` parameter. Only ever `true` for dedicated blocks.
+    pub fn device_address(&self) -> bool {
+        match self.flavor {
+            BlockFlavor::Dedicated(ref block) => block.device_address(),
+            BlockFlavor::General(_) | BlockFlavor::Linear(_) | BlockFlavor::FreeList(_) => false,
+        }
+    }
 }
 
 #[derive(Debug)]
-enum BlockFlavor<B: hal::Backend> {
+pub(crate) enum BlockFlavor<B: hal::Backend> {
     Dedicated(DedicatedBlock<B>),
     General(GeneralBlock<B>),
     Linear(LinearBlock<B>),
+    FreeList(FreeListBlock<B>),
 }
 
 impl<B: hal::Backend> BlockFlavor<B> {
@@ -246,6 +423,7 @@ impl<B: hal::Backend> BlockFlavor<B> {
             BlockFlavor::Dedicated(block) => block.size(),
             BlockFlavor::General(block) => block.size(),
             BlockFlavor::Linear(block) => block.size(),
+            BlockFlavor::FreeList(block) => block.size(),
         }
     }
 }
@@ -256,6 +434,7 @@ impl<B: hal::Backend> Block<B> for MemoryBlock<B> {
             BlockFlavor::Dedicated(ref block) => block.properties(),
             BlockFlavor::General(ref block) => block.properties(),
             BlockFlavor::Linear(ref block) => block.properties(),
+            BlockFlavor::FreeList(ref block) => block.properties(),
         }
     }
 
@@ -264,6 +443,7 @@ impl<B: hal::Backend> Block<B> for MemoryBlock<B> {
             BlockFlavor::Dedicated(ref block) => block.memory(),
             BlockFlavor::General(ref block) => block.memory(),
             BlockFlavor::Linear(ref block) => block.memory(),
+            BlockFlavor::FreeList(ref block) => block.memory(),
         }
     }
 
@@ -272,6 +452,7 @@ impl<B: hal::Backend> Block<B> for MemoryBlock<B> {
             BlockFlavor::Dedicated(ref block) => block.segment(),
             BlockFlavor::General(ref block) => block.segment(),
             BlockFlavor::Linear(ref block) => block.segment(),
+            BlockFlavor::FreeList(ref block) => block.segment(),
         }
     }
 
@@ -284,6 +465,24 @@ impl<B: hal::Backend> Block<B> for MemoryBlock<B> {
             BlockFlavor::Dedicated(ref mut block) => block.map(device, segment),
             BlockFlavor::General(ref mut block) => block.map(device, segment),
             BlockFlavor::Linear(ref mut block) => block.map(device, segment),
+            BlockFlavor::FreeList(ref mut block) => block.map(device, segment),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_address_allowed_without_the_flag_regardless_of_support() {
+        assert!(device_address_allowed(false, false));
+        assert!(device_address_allowed(false, true));
+    }
+
+    #[test]
+    fn device_address_allowed_with_the_flag_only_when_supported() {
+        assert!(device_address_allowed(true, true));
+        assert!(!device_address_allowed(true, false));
+    }
+}
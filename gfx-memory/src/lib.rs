@@ -0,0 +1,28 @@
+//! Memory allocation helpers built on top of `gfx-hal`.
+//!
+//! `Heaps` is the entry point: it tracks the physical heaps and memory types
+//! reported by a device and dispatches allocation requests to one of a
+//! handful of sub-allocator flavors depending on the requested usage and
+//! size.
+
+mod allocator;
+mod block;
+mod heaps;
+mod mapping;
+mod report;
+mod stats;
+mod usage;
+mod util;
+
+pub use self::{
+    allocator::*,
+    block::Block,
+    heaps::{Dedicated, Heaps, HeapsConfig, HeapsError, MemoryBlock},
+    mapping::MappedRange,
+    report::*,
+    stats::*,
+    usage::MemoryUsage,
+};
+
+/// Size type used throughout this crate for offsets, sizes and alignments.
+pub type Size = u64;
@@ -0,0 +1,43 @@
+//! A mapped view into device memory.
+
+use crate::Size;
+
+/// A host-visible view into a range of device memory, valid for the lifetime
+/// of the borrow of the block it was created from.
+#[derive(Debug)]
+pub struct MappedRange<'a, B: hal::Backend> {
+    memory: &'a B::Memory,
+    segment: hal::memory::Segment,
+    ptr: std::ptr::NonNull<u8>,
+}
+
+impl<'a, B: hal::Backend> MappedRange<'a, B> {
+    pub(crate) fn new(
+        memory: &'a B::Memory,
+        segment: hal::memory::Segment,
+        ptr: std::ptr::NonNull<u8>,
+    ) -> Self {
+        MappedRange {
+            memory,
+            segment,
+            ptr,
+        }
+    }
+
+    /// Get the memory object this range was mapped from.
+    pub fn memory(&self) -> &B::Memory {
+        self.memory
+    }
+
+    /// Get a pointer to the start of the mapped range.
+    pub fn ptr(&self) -> std::ptr::NonNull<u8> {
+        self.ptr
+    }
+
+    /// Get the size of the mapped range. Returns `0` for a range mapped with
+    /// an open-ended segment (`size: None`, e.g. a dedicated block's
+    /// `Segment::ALL`).
+    pub fn size(&self) -> Size {
+        self.segment.size.unwrap_or(0)
+    }
+}
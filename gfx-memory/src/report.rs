@@ -0,0 +1,58 @@
+//! A structured snapshot of every live allocation known to `Heaps`.
+//!
+//! Unlike `TotalMemoryUtilization`'s aggregate byte counters, this walks down
+//! to individual named sub-blocks so two snapshots can be diffed between
+//! frames to track down what is growing.
+
+use crate::Size;
+
+/// A single live sub-block within a chunk.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BlockReport {
+    /// Offset of the block within its chunk.
+    pub offset: Size,
+    /// Size of the block.
+    pub size: Size,
+    /// Name passed to `Heaps::allocate`, if any.
+    pub name: Option<String>,
+}
+
+/// One `hal::Memory` object and the live sub-blocks carved out of it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ChunkReport {
+    /// Size of the chunk, i.e. the `hal::Memory` object's size.
+    pub size: Size,
+    /// Live sub-blocks carved out of the chunk.
+    pub blocks: Vec<BlockReport>,
+}
+
+/// All chunks (and standalone dedicated allocations) reserved by one memory type.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MemoryTypeReport {
+    /// Chunks reserved by this memory type's sub-allocators, plus one
+    /// single-block pseudo-chunk per dedicated allocation.
+    pub chunks: Vec<ChunkReport>,
+}
+
+/// Byte totals for one heap.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MemoryHeapReport {
+    /// Total size of the heap.
+    pub total: Size,
+    /// Bytes currently reserved from the device.
+    pub used: Size,
+}
+
+/// A full snapshot of every live allocation known to a `Heaps`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MemoryReport {
+    /// Per-heap totals, indexed the same as passed to `Heaps::new`.
+    pub heaps: Vec<MemoryHeapReport>,
+    /// Per-memory-type chunk breakdown, indexed the same as passed to `Heaps::new`.
+    pub types: Vec<MemoryTypeReport>,
+}
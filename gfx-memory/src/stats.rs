@@ -0,0 +1,33 @@
+//! Utilization snapshots returned by `Heaps::utilization`.
+
+use crate::Size;
+
+/// Bytes claimed by live blocks versus bytes actually reserved from the
+/// device to back them.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryUtilization {
+    /// Bytes requested by live blocks.
+    pub used: Size,
+    /// Bytes actually reserved from the device (chunks, dedicated allocations).
+    pub effective: Size,
+}
+
+/// Utilization of a single heap.
+pub type MemoryHeapUtilization = MemoryUtilization;
+
+/// Utilization of a single memory type.
+pub type MemoryTypeUtilization = MemoryUtilization;
+
+/// Aggregate utilization across every heap and memory type known to `Heaps`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TotalMemoryUtilization {
+    /// Utilization of each heap, indexed the same as passed to `Heaps::new`.
+    pub heaps: Vec<MemoryHeapUtilization>,
+    /// Utilization of each memory type, indexed the same as passed to `Heaps::new`.
+    pub types: Vec<MemoryTypeUtilization>,
+    /// Remaining budget for new `hal::Memory` objects before hitting the
+    /// device's `maxMemoryAllocationCount` limit.
+    pub allocations_remains: u32,
+}
@@ -0,0 +1,62 @@
+//! Usage hints that bias memory type selection and sub-allocator choice.
+
+use hal::memory::Properties;
+
+/// A hint describing how a resource backed by an allocated block is going to
+/// be used. Drives both which memory type `Heaps::allocate` picks and which
+/// sub-allocator flavor within that type is preferred.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryUsage {
+    /// Device-local memory for resources that are written once (or rarely)
+    /// and read many times by the device, e.g. textures and vertex buffers.
+    Data,
+    /// Host-visible memory written by the host and consumed by the device,
+    /// typically updated every frame.
+    Dynamic,
+    /// Host-visible memory written once by the host, e.g. staging buffers
+    /// used to upload data to device-local memory.
+    Upload,
+    /// Host-visible memory written by the device and read back by the host.
+    Download,
+}
+
+impl MemoryUsage {
+    /// Properties that a memory type must have to be considered at all.
+    pub fn properties_required(&self) -> Properties {
+        match self {
+            MemoryUsage::Data => Properties::DEVICE_LOCAL,
+            MemoryUsage::Dynamic | MemoryUsage::Upload | MemoryUsage::Download => {
+                Properties::CPU_VISIBLE
+            }
+        }
+    }
+
+    /// Rank a candidate memory type's properties for this usage. Higher is better.
+    pub fn memory_fitness(&self, properties: Properties) -> u32 {
+        match self {
+            MemoryUsage::Data => u32::from(properties.contains(Properties::DEVICE_LOCAL)),
+            MemoryUsage::Dynamic => u32::from(!properties.contains(Properties::DEVICE_LOCAL)),
+            MemoryUsage::Upload => {
+                u32::from(properties.contains(Properties::DEVICE_LOCAL))
+                    + u32::from(!properties.contains(Properties::CPU_CACHED))
+            }
+            MemoryUsage::Download => {
+                u32::from(properties.contains(Properties::CPU_CACHED))
+                    + u32::from(!properties.contains(Properties::DEVICE_LOCAL))
+            }
+        }
+    }
+
+    /// Whether resources with this usage are expected to be short-lived,
+    /// making them a good fit for the linear sub-allocator.
+    pub(crate) fn is_transient(&self) -> bool {
+        matches!(self, MemoryUsage::Dynamic | MemoryUsage::Upload)
+    }
+
+    /// Whether resources with this usage are long-lived device-local data,
+    /// making them a good fit for the free-list sub-allocator over the
+    /// buddy allocator's power-of-two rounding.
+    pub(crate) fn prefers_free_list(&self) -> bool {
+        matches!(self, MemoryUsage::Data)
+    }
+}
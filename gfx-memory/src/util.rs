@@ -0,0 +1,30 @@
+//! Small helpers shared by the sub-allocators.
+
+use crate::Size;
+
+/// Round `value` up to the nearest multiple of `align`. `align` of `0` is
+/// treated as `1` (no alignment requirement).
+pub(crate) fn align_up(value: Size, align: Size) -> Size {
+    if align <= 1 {
+        return value;
+    }
+    (value + align - 1) / align * align
+}
+
+/// `hal::memory::AllocationFlags` to pass to `Device::allocate_memory` for a
+/// request with the given `device_address` hint. Centralized so every
+/// `allocate_memory` call site constructs (and is seen to construct) the
+/// flags explicitly, rather than some passing the flag and others silently
+/// dropping it.
+///
+/// The sub-allocators (`linear`, `general`, `free_list`) always pass `false`
+/// here: `MemoryType::alloc` routes every `device_address` request to a
+/// dedicated allocation instead, since a chunk shared by sub-blocks of mixed
+/// purpose can never carry the flag on their behalf.
+pub(crate) fn allocation_flags(device_address: bool) -> hal::memory::AllocationFlags {
+    if device_address {
+        hal::memory::AllocationFlags::DEVICE_ADDRESS
+    } else {
+        hal::memory::AllocationFlags::empty()
+    }
+}